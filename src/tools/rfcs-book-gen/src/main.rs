@@ -1,51 +1,542 @@
-use std::{env, error, fs, path};
+use std::{env, error, fs, io, path};
 use std::io::Write;
 
-fn run<S, D>(src_path: S, dest_path: D) -> Result<(), Box<error::Error>>
+/// How to handle a chapter whose destination file already exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CopyMode {
+    /// Always (re)write the destination.
+    Overwrite,
+    /// Only (re)write when the source is newer than the existing destination.
+    Update,
+    /// Leave an existing destination untouched.
+    Skip,
+}
+
+/// A single file that failed to read or write during a `run()` pass. These
+/// are collected rather than aborting the whole build on the first failure.
+#[derive(Debug)]
+struct CopyError {
+    path: path::PathBuf,
+    error: io::Error,
+}
+
+/// Whether `dest` should be (re)written given `mode`, comparing mtimes
+/// against `src` for `CopyMode::Update`.
+fn should_write(src: &path::Path, dest: &path::Path, mode: CopyMode) -> bool {
+    if !dest.exists() {
+        return true;
+    }
+
+    match mode {
+        CopyMode::Overwrite => true,
+        CopyMode::Skip => false,
+        CopyMode::Update => {
+            let src_modified = fs::metadata(src).and_then(|m| m.modified());
+            let dest_modified = fs::metadata(dest).and_then(|m| m.modified());
+            match (src_modified, dest_modified) {
+                (Ok(s), Ok(d)) => s > d,
+                _ => true,
+            }
+        }
+    }
+}
+
+/// Known source formats an RFC file may be written in. A recognized but
+/// otherwise unmapped extension (one a caller opted into via the
+/// `extensions` parameter of `run()`) defaults to `PlainText` so opting in
+/// actually extends what gets processed rather than silently dropping it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FileFormat {
+    Markdown,
+    AsciiDoc,
+    PlainText,
+}
+
+impl FileFormat {
+    fn from_extension(ext: &str) -> FileFormat {
+        match ext.to_lowercase().as_str() {
+            "md" | "markdown" => FileFormat::Markdown,
+            "adoc" | "asciidoc" => FileFormat::AsciiDoc,
+            _ => FileFormat::PlainText,
+        }
+    }
+}
+
+/// Extensions `main()` passes to `run()`; `run()` itself always takes the
+/// list from its `extensions` parameter.
+const DEFAULT_EXTENSIONS: &[&str] = &["md", "markdown", "adoc", "asciidoc", "txt"];
+
+/// Normalizes a recognized, non-Markdown file into a Markdown chapter so it
+/// still links correctly from `SUMMARY.md`. Full format conversion is out of
+/// scope here, so the original contents are just wrapped in a fenced block.
+fn normalize_to_markdown(format: FileFormat, contents: &str) -> String {
+    match format {
+        FileFormat::Markdown => contents.to_owned(),
+        FileFormat::AsciiDoc => format!("```asciidoc\n{}\n```\n", contents),
+        FileFormat::PlainText => format!("```text\n{}\n```\n", contents),
+    }
+}
+
+/// Pulls a human-readable title for a chapter out of its own contents,
+/// preferring a YAML/TOML front-matter `title:` field, then a `# Heading`
+/// on the first non-blank line after any front matter, and falling back to
+/// `fallback` (the cleaned filename) when neither is present. A heading
+/// further down the file is not used.
+fn extract_title(contents: &str, fallback: &str) -> String {
+    extract_front_matter_title(contents)
+        .or_else(|| extract_heading_title(contents))
+        .unwrap_or_else(|| fallback.to_owned())
+}
+
+fn extract_front_matter_title(contents: &str) -> Option<String> {
+    let mut lines = contents.lines();
+    let delimiter = match lines.next().unwrap_or("").trim() {
+        "---" => "---",
+        "+++" => "+++",
+        _ => return None,
+    };
+
+    for line in lines {
+        if line.trim() == delimiter {
+            break;
+        }
+
+        let trimmed = line.trim();
+        if trimmed.starts_with("title:") || trimmed.starts_with("title =") {
+            let value = trimmed
+                .split_once(|c: char| c == ':' || c == '=')
+                .map(|(_, rest)| rest)
+                .unwrap_or("")
+                .trim()
+                .trim_matches('"')
+                .trim_matches('\'');
+            if !value.is_empty() {
+                return Some(value.to_owned());
+            }
+        }
+    }
+
+    None
+}
+
+fn extract_heading_title(contents: &str) -> Option<String> {
+    for line in skip_front_matter(contents).lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        if trimmed.starts_with("# ") {
+            return Some(trimmed.trim_start_matches("# ").trim().to_owned());
+        }
+        break;
+    }
+
+    None
+}
+
+/// Returns `contents` with a leading YAML/TOML front-matter block (if any)
+/// removed, so heading detection doesn't mistake its `---`/`+++` delimiter
+/// for "no heading here" on a file that has a real heading just below it.
+fn skip_front_matter(contents: &str) -> &str {
+    let mut lines = contents.lines();
+    let first = match lines.next() {
+        Some(line) => line,
+        None => return contents,
+    };
+
+    let delimiter = match first.trim() {
+        "---" => "---",
+        "+++" => "+++",
+        _ => return contents,
+    };
+
+    let mut offset = first.len() + 1;
+    for line in lines {
+        offset += line.len() + 1;
+        if line.trim() == delimiter {
+            return contents.get(offset..).unwrap_or("");
+        }
+    }
+
+    contents
+}
+
+/// Read-only options for a `visit_dir` walk, threaded through the recursion
+/// unchanged.
+struct WalkConfig<'a> {
+    extensions: &'a [&'a str],
+    copy_mode: CopyMode,
+}
+
+/// Mutable accumulators a `visit_dir` walk writes into as it recurses.
+struct WalkState<'a> {
+    buf: &'a mut String,
+    errors: &'a mut Vec<CopyError>,
+}
+
+fn visit_dir<S, D>(
+    src_path: S,
+    dest_path: D,
+    rel_prefix: &path::Path,
+    depth: usize,
+    config: &WalkConfig,
+    state: &mut WalkState,
+) -> Result<(), Box<error::Error>>
 where
     S: AsRef<path::Path>,
     D: AsRef<path::Path>,
 {
-    let mut buf = String::new();
-    buf.push_str("# RFCS\n\n");
-
-    fs::create_dir_all(&dest_path)?;
+    fs::create_dir_all(dest_path.as_ref())?;
 
-    let mut rfcs_file_names = src_path
+    let mut entries = src_path
         .as_ref()
         .read_dir()?
-        .map(|d| d.unwrap().path().file_name().unwrap().to_owned())
+        .map(|d| d.unwrap().path())
         .collect::<Vec<_>>();
 
-    rfcs_file_names.sort();
+    entries.sort();
 
-    for i in rfcs_file_names {
-        buf.push_str(&format!(
-            "- [{}]({})\n",
-            i.to_str().unwrap().trim_right_matches(".md"),
-            i.to_str().unwrap()
-        ));
-    }
+    let indent = "  ".repeat(depth);
 
-    let mut file = fs::File::create(dest_path.as_ref().join("SUMMARY.md"))?;
-    file.write_all(buf.as_bytes())?;
+    for entry in entries {
+        let file_name = entry.file_name().unwrap().to_owned();
+
+        if entry.is_dir() {
+            // mdBook's SUMMARY.md grammar requires every list item, nested or
+            // not, to be a link; an empty href marks it a draft chapter.
+            state.buf.push_str(&format!("{}- [{}]()\n", indent, file_name.to_str().unwrap()));
+            visit_dir(
+                &entry,
+                dest_path.as_ref().join(&file_name),
+                &rel_prefix.join(&file_name),
+                depth + 1,
+                config,
+                state,
+            )?;
+            continue;
+        }
+
+        let ext = match entry.extension().and_then(|e| e.to_str()) {
+            Some(ext) => ext,
+            None => continue,
+        };
 
-    for d in src_path.as_ref().read_dir()? {
-        let d = d.unwrap();
-        fs::copy(d.path(), dest_path.as_ref().join(d.file_name())).expect("could not copy");
+        if !config.extensions.contains(&ext.to_lowercase().as_str()) {
+            continue;
+        }
+
+        let format = FileFormat::from_extension(ext);
+
+        let stem = entry.file_stem().unwrap().to_str().unwrap();
+        // Keep the original extension in the destination name (except for
+        // `.md` itself) so two files that share a stem but differ in
+        // recognized extension, e.g. `0001-foo.md` and `0001-foo.txt`,
+        // don't collide on the same destination path.
+        let dest_file_name = if ext.eq_ignore_ascii_case("md") {
+            format!("{}.md", stem)
+        } else {
+            format!("{}.{}.md", stem, ext)
+        };
+        let rel_path = rel_prefix.join(&dest_file_name);
+        let dest_file_path = dest_path.as_ref().join(&dest_file_name);
+
+        let contents = match fs::read_to_string(&entry) {
+            Ok(contents) => contents,
+            Err(e) => {
+                state.errors.push(CopyError {
+                    path: entry.clone(),
+                    error: e,
+                });
+                continue;
+            }
+        };
+        let title = extract_title(&contents, stem);
+
+        state.buf.push_str(&format!("{}- [{}]({})\n", indent, title, rel_path.to_str().unwrap()));
+
+        if !should_write(&entry, &dest_file_path, config.copy_mode) {
+            continue;
+        }
+
+        let write_result = fs::File::create(&dest_file_path)
+            .and_then(|mut file| file.write_all(normalize_to_markdown(format, &contents).as_bytes()));
+        if let Err(e) = write_result {
+            state.errors.push(CopyError {
+                path: dest_file_path,
+                error: e,
+            });
+        }
     }
 
     Ok(())
 }
 
+/// Resolves `path` against `root` when `path` is relative and a `root` was
+/// given, rather than leaving it to implicitly resolve against the process's
+/// current directory.
+fn resolve_path<P: AsRef<path::Path>>(root: Option<&path::Path>, path: P) -> path::PathBuf {
+    let path = path.as_ref();
+    match root {
+        Some(root) if path.is_relative() => root.join(path),
+        _ => path.to_path_buf(),
+    }
+}
+
+fn run<S, D>(
+    root: Option<&path::Path>,
+    src_path: S,
+    dest_path: D,
+    extensions: &[&str],
+    copy_mode: CopyMode,
+) -> Result<Vec<CopyError>, Box<error::Error>>
+where
+    S: AsRef<path::Path>,
+    D: AsRef<path::Path>,
+{
+    let src_path = resolve_path(root, src_path);
+    let dest_path = resolve_path(root, dest_path);
+
+    let mut buf = String::new();
+    buf.push_str("# RFCS\n\n");
+
+    fs::create_dir_all(&dest_path)?;
+
+    let mut errors = Vec::new();
+    let config = WalkConfig { extensions, copy_mode };
+    let mut state = WalkState {
+        buf: &mut buf,
+        errors: &mut errors,
+    };
+
+    visit_dir(&src_path, &dest_path, path::Path::new(""), 0, &config, &mut state)?;
+
+    let mut file = fs::File::create(dest_path.join("SUMMARY.md"))?;
+    file.write_all(buf.as_bytes())?;
+
+    Ok(errors)
+}
+
+fn parse_copy_mode(value: &str) -> CopyMode {
+    match value {
+        "overwrite" => CopyMode::Overwrite,
+        "update" => CopyMode::Update,
+        "skip" => CopyMode::Skip,
+        _ => panic!("--copy-mode must be one of: overwrite, update, skip"),
+    }
+}
+
 fn main() {
-    let src_path_str = env::args_os().skip(1).next().expect("source path required");
+    let mut root = None;
+    let mut copy_mode = CopyMode::Overwrite;
+    let mut positional = Vec::new();
+
+    let mut args = env::args_os().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some("--root") => {
+                let value = args.next().expect("--root requires a path argument");
+                root = Some(path::PathBuf::from(value));
+            }
+            Some("--copy-mode") => {
+                let value = args.next().expect("--copy-mode requires a value");
+                copy_mode = parse_copy_mode(value.to_str().expect("--copy-mode value must be UTF-8"));
+            }
+            _ => positional.push(arg),
+        }
+    }
+
+    let mut positional = positional.into_iter();
+
+    let src_path_str = positional.next().expect("source path required");
     let src_path = path::Path::new(&src_path_str);
 
-    let dest_path_str = env::args_os().skip(2).next().expect(
-        "destination path required",
-    );
+    let dest_path_str = positional.next().expect("destination path required");
     let dest_path = path::Path::new(&dest_path_str).join("src");
 
-    run(src_path, dest_path).unwrap();
+    let errors = run(
+        root.as_deref(),
+        src_path,
+        dest_path,
+        DEFAULT_EXTENSIONS,
+        copy_mode,
+    ).unwrap();
+    for error in &errors {
+        eprintln!("error processing {}: {}", error.path.display(), error.error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn front_matter_title_wins_over_heading() {
+        let contents = "---\ntitle: Front Matter Title\n---\n# Heading Title\n";
+        assert_eq!(extract_title(contents, "fallback"), "Front Matter Title");
+    }
+
+    #[test]
+    fn heading_title_used_when_no_front_matter() {
+        let contents = "\n# Heading Title\nbody\n";
+        assert_eq!(extract_title(contents, "fallback"), "Heading Title");
+    }
+
+    #[test]
+    fn fallback_used_when_neither_present() {
+        let contents = "just a paragraph, no heading\n";
+        assert_eq!(extract_title(contents, "fallback"), "fallback");
+    }
+
+    #[test]
+    fn front_matter_title_supports_toml_delimiters() {
+        let contents = "+++\ntitle = \"Toml Title\"\n+++\nbody\n";
+        assert_eq!(
+            extract_front_matter_title(contents),
+            Some("Toml Title".to_owned())
+        );
+    }
+
+    #[test]
+    fn front_matter_without_title_field_falls_through() {
+        let contents = "---\nauthor: someone\n---\n";
+        assert_eq!(extract_front_matter_title(contents), None);
+        assert_eq!(extract_title(contents, "fallback"), "fallback");
+    }
+
+    #[test]
+    fn heading_must_be_the_first_non_blank_line() {
+        let contents = "not a heading\n# Too Late\n";
+        assert_eq!(extract_heading_title(contents), None);
+    }
+
+    #[test]
+    fn heading_found_after_front_matter_without_title() {
+        let contents = "---\nauthor: bob\n---\n# Actual Heading\n";
+        assert_eq!(extract_title(contents, "fallback"), "Actual Heading");
+    }
+
+    fn temp_dir(name: &str) -> path::PathBuf {
+        let dir = env::temp_dir().join(format!("rfcs-book-gen-test-{}", name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn should_write_overwrite_always_true() {
+        let dir = temp_dir("overwrite");
+        let src = dir.join("src.md");
+        let dest = dir.join("dest.md");
+        fs::write(&src, "a").unwrap();
+        fs::write(&dest, "b").unwrap();
+        assert!(should_write(&src, &dest, CopyMode::Overwrite));
+    }
+
+    #[test]
+    fn should_write_skip_leaves_existing_destination() {
+        let dir = temp_dir("skip-existing");
+        let src = dir.join("src.md");
+        let dest = dir.join("dest.md");
+        fs::write(&src, "a").unwrap();
+        fs::write(&dest, "b").unwrap();
+        assert!(!should_write(&src, &dest, CopyMode::Skip));
+    }
+
+    #[test]
+    fn should_write_true_when_destination_missing() {
+        let dir = temp_dir("missing-dest");
+        let src = dir.join("src.md");
+        let dest = dir.join("dest.md");
+        fs::write(&src, "a").unwrap();
+        assert!(should_write(&src, &dest, CopyMode::Skip));
+        assert!(should_write(&src, &dest, CopyMode::Update));
+    }
+
+    #[test]
+    fn should_write_update_false_when_destination_not_older() {
+        let dir = temp_dir("update-not-older");
+        let path = dir.join("same.md");
+        fs::write(&path, "a").unwrap();
+        // Comparing a file against itself: identical mtimes are never "newer".
+        assert!(!should_write(&path, &path, CopyMode::Update));
+    }
+
+    #[test]
+    fn run_nests_subdirectories_in_summary() {
+        let dir = temp_dir("recursive-walk");
+        let src = dir.join("src");
+        let dest = dir.join("dest");
+        fs::create_dir_all(src.join("lang")).unwrap();
+        fs::write(src.join("lang").join("0001-foo.md"), "# Foo\n").unwrap();
+
+        let errors = run(None, &src, &dest, DEFAULT_EXTENSIONS, CopyMode::Overwrite).unwrap();
+        assert!(errors.is_empty());
+
+        let summary = fs::read_to_string(dest.join("SUMMARY.md")).unwrap();
+        assert!(summary.contains("- [lang]()\n"));
+        assert!(summary.contains("  - [Foo](lang/0001-foo.md)\n"));
+        assert!(dest.join("lang").join("0001-foo.md").exists());
+    }
+
+    #[test]
+    fn run_opts_into_extra_format_via_extensions_param() {
+        let dir = temp_dir("extra-extension");
+        let src = dir.join("src");
+        let dest = dir.join("dest");
+        fs::create_dir_all(&src).unwrap();
+        fs::write(src.join("0004-extra.rst"), "Extra content").unwrap();
+
+        let extensions = ["md", "rst"];
+        let errors = run(None, &src, &dest, &extensions, CopyMode::Overwrite).unwrap();
+        assert!(errors.is_empty());
+
+        let summary = fs::read_to_string(dest.join("SUMMARY.md")).unwrap();
+        assert!(summary.contains("0004-extra.rst.md"));
+
+        let chapter = fs::read_to_string(dest.join("0004-extra.rst.md")).unwrap();
+        assert!(chapter.contains("Extra content"));
+    }
+
+    #[test]
+    fn run_skips_files_with_unrecognized_extensions() {
+        let dir = temp_dir("skip-unrelated");
+        let src = dir.join("src");
+        let dest = dir.join("dest");
+        fs::create_dir_all(&src).unwrap();
+        fs::write(src.join("0001-foo.md"), "# Foo\n").unwrap();
+        fs::write(src.join("image.png"), "binary").unwrap();
+
+        let errors = run(None, &src, &dest, DEFAULT_EXTENSIONS, CopyMode::Overwrite).unwrap();
+        assert!(errors.is_empty());
+
+        let summary = fs::read_to_string(dest.join("SUMMARY.md")).unwrap();
+        assert!(!summary.contains("image"));
+        assert!(!dest.join("image.png.md").exists());
+    }
+
+    #[test]
+    fn run_resolves_relative_paths_against_root() {
+        let dir = temp_dir("root-resolution");
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("src").join("0001-foo.md"), "# Foo\n").unwrap();
+
+        let errors = run(
+            Some(dir.as_path()),
+            path::Path::new("src"),
+            path::Path::new("dest"),
+            DEFAULT_EXTENSIONS,
+            CopyMode::Overwrite,
+        ).unwrap();
+        assert!(errors.is_empty());
+
+        let summary = fs::read_to_string(dir.join("dest").join("SUMMARY.md")).unwrap();
+        assert!(summary.contains("[Foo](0001-foo.md)"));
+    }
+
+    #[test]
+    fn resolve_path_keeps_absolute_paths_unchanged() {
+        let dir = temp_dir("root-absolute");
+        let absolute = dir.join("already-absolute");
+        assert_eq!(resolve_path(Some(dir.as_path()), &absolute), absolute);
+    }
 }